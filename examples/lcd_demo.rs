@@ -1,6 +1,7 @@
 use std::{error::Error, thread::sleep, time::Duration};
 
 use char_lcd_rgb_i2c::{CharLCDRGBI2C, LcdError};
+use rppal::i2c::{Error as I2cError, I2c};
 
 fn main() -> Result<(), Box<dyn Error>> {
     println!("Setting up I2C and RGB1602 LCD...");
@@ -15,14 +16,14 @@ fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-fn hello_world_demo(lcd: &mut CharLCDRGBI2C) -> Result<(), LcdError> {
+fn hello_world_demo(lcd: &mut CharLCDRGBI2C<I2c>) -> Result<(), LcdError<I2cError>> {
     println!("Starting Hello World demo");
 
     lcd.message("Hello World!")?;
     Ok(())
 }
 
-fn backlight_demo(lcd: &mut CharLCDRGBI2C) -> Result<(), LcdError> {
+fn backlight_demo(lcd: &mut CharLCDRGBI2C<I2c>) -> Result<(), LcdError<I2cError>> {
     println!("Starting Backlight demo");
 
     println!("Turning backlight OFF");
@@ -41,7 +42,7 @@ fn backlight_demo(lcd: &mut CharLCDRGBI2C) -> Result<(), LcdError> {
     Ok(())
 }
 
-fn led_demo(lcd: &mut CharLCDRGBI2C) -> Result<(), LcdError> {
+fn led_demo(lcd: &mut CharLCDRGBI2C<I2c>) -> Result<(), LcdError<I2cError>> {
     println!("Starting RGB LED Demo");
 
     // Define a map of colors
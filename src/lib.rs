@@ -1,7 +1,7 @@
 use std::{error::Error, fmt, thread::sleep, time::Duration};
 
+use embedded_hal::blocking::i2c::{Write, WriteRead};
 use mcp230xx::{Direction, Level, Mcp23017, Mcp230xx, PullUp};
-use rppal::i2c::I2c;
 
 pub const ADDR: u8 = 0x20; // Default I2C address
 
@@ -46,6 +46,7 @@ pub const LCD_SETDDRAMADDR: u8 = 0x80;
 // Entry flags
 pub const LCD_ENTRYLEFT: u8 = 0x02;
 pub const LCD_ENTRYSHIFTDECREMENT: u8 = 0x00;
+pub const LCD_ENTRYSHIFTINCREMENT: u8 = 0x01;
 
 // Control flags
 pub const LCD_DISPLAYON: u8 = 0x04;
@@ -64,6 +65,7 @@ pub const LCD_4BITMODE: u8 = 0x00;
 pub const LCD_2LINE: u8 = 0x08;
 pub const LCD_1LINE: u8 = 0x00;
 pub const LCD_5X8DOTS: u8 = 0x00;
+pub const LCD_5X10DOTS: u8 = 0x04;
 
 // Direction constants
 pub const LEFT_TO_RIGHT: usize = 0;
@@ -72,21 +74,80 @@ pub const RIGHT_TO_LEFT: usize = 1;
 // Row offset addresses for different LCD lines
 pub const LCD_ROW_OFFSETS: [u8; 4] = [0x00, 0x40, 0x14, 0x54];
 
-// Custom error type
+/// Identifies one of the five front-panel buttons.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    Left,
+    Up,
+    Down,
+    Right,
+    Select,
+}
+
+/// A snapshot of which front-panel buttons are currently pressed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Buttons {
+    pub left: bool,
+    pub up: bool,
+    pub down: bool,
+    pub right: bool,
+    pub select: bool,
+}
+
+impl Buttons {
+    /// Returns whether `button` is pressed in this snapshot.
+    pub fn is_pressed(&self, button: Button) -> bool {
+        match button {
+            Button::Left => self.left,
+            Button::Up => self.up,
+            Button::Down => self.down,
+            Button::Right => self.right,
+            Button::Select => self.select,
+        }
+    }
+}
+
+/// A button transitioning between pressed and released, as reported by `poll_events`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ButtonEvent {
+    Press(Button),
+    Release(Button),
+}
+
+/// HD44780 character cell size, selected via the function-set command.
+///
+/// `Font5x10Dots` is only valid on a single-line display; the HD44780
+/// controller has no 5x10 cell in 2-line mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontSize {
+    Font5x8Dots,
+    Font5x10Dots,
+}
+
+impl FontSize {
+    fn function_set_bits(self) -> u8 {
+        match self {
+            FontSize::Font5x8Dots => LCD_5X8DOTS,
+            FontSize::Font5x10Dots => LCD_5X10DOTS,
+        }
+    }
+}
+
+// Custom error type, generic over the underlying I2C bus's error type.
 #[derive(Debug)]
-pub enum LcdError {
-    I2c(rppal::i2c::Error),
+pub enum LcdError<E> {
+    I2c(E),
     Mcp(String),
     Other(String),
 }
 
-impl From<rppal::i2c::Error> for LcdError {
-    fn from(err: rppal::i2c::Error) -> Self {
+impl<E> From<E> for LcdError<E> {
+    fn from(err: E) -> Self {
         LcdError::I2c(err)
     }
 }
 
-impl fmt::Display for LcdError {
+impl<E: fmt::Display> fmt::Display for LcdError<E> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             LcdError::I2c(err) => write!(f, "I2C error: {}", err),
@@ -96,10 +157,10 @@ impl fmt::Display for LcdError {
     }
 }
 
-impl Error for LcdError {}
+impl<E: fmt::Debug + fmt::Display> Error for LcdError<E> {}
 
-pub struct CharLCDRGBI2C {
-    mcp: Mcp230xx<I2c, Mcp23017>,
+pub struct CharLCDRGBI2C<I> {
+    mcp: Mcp230xx<I, Mcp23017>,
     columns: usize,
     lines: usize,
     backlight: bool,    // Backlight status
@@ -112,16 +173,70 @@ pub struct CharLCDRGBI2C {
     column: usize,
     column_align: bool,
     message: String,
-    direction: usize,
+    direction: u8, // last scroll direction: LCD_MOVELEFT or LCD_MOVERIGHT
+    buttons_raw: Buttons,    // last raw sample, used to debounce
+    buttons_stable: Buttons, // last sample confirmed stable across two polls
+    busy_polling: bool,      // whether wait_ready polls the busy flag over LCD_RW
+}
+
+#[cfg(feature = "rppal")]
+impl CharLCDRGBI2C<rppal::i2c::I2c> {
+    /// Convenience constructor for Raspberry Pi users: opens the Pi's I2C bus
+    /// via `rppal` and talks to the MCP23017 at its default address, using
+    /// the standard 5x8 font.
+    pub fn new(columns: usize, lines: usize) -> Result<Self, LcdError<rppal::i2c::Error>> {
+        Self::new_with_config(columns, lines, FontSize::Font5x8Dots)
+    }
+
+    /// Like `new`, but lets the caller pick the character font.
+    pub fn new_with_config(
+        columns: usize,
+        lines: usize,
+        font: FontSize,
+    ) -> Result<Self, LcdError<rppal::i2c::Error>> {
+        let i2c = rppal::i2c::I2c::new().map_err(LcdError::I2c)?;
+        Self::new_with_bus_config(i2c, ADDR, columns, lines, font)
+    }
 }
 
-impl CharLCDRGBI2C {
-    pub fn new(columns: usize, lines: usize) -> Result<Self, LcdError> {
-        // Initialize I2C
-        let i2c = I2c::new()?;
+impl<I, E> CharLCDRGBI2C<I>
+where
+    I: WriteRead<Error = E> + Write<Error = E>,
+    E: fmt::Debug,
+{
+    /// Builds a driver on top of a pre-built `embedded-hal` I2C bus, talking
+    /// to the MCP23017 at `address` and using the standard 5x8 font. Use this
+    /// to target platforms other than the Raspberry Pi (ESP32, STM32, Linux
+    /// `i2cdev`, etc.); Pi users can instead use the `new` convenience
+    /// constructor behind the `rppal` feature.
+    pub fn new_with_bus(
+        i2c: I,
+        address: u8,
+        columns: usize,
+        lines: usize,
+    ) -> Result<Self, LcdError<E>> {
+        Self::new_with_bus_config(i2c, address, columns, lines, FontSize::Font5x8Dots)
+    }
+
+    /// Like `new_with_bus`, but lets the caller pick the character font.
+    ///
+    /// `FontSize::Font5x10Dots` is only valid when `lines` is 1; the HD44780
+    /// has no 5x10 cell in 2-line mode.
+    pub fn new_with_bus_config(
+        i2c: I,
+        address: u8,
+        columns: usize,
+        lines: usize,
+        font: FontSize,
+    ) -> Result<Self, LcdError<E>> {
+        if font == FontSize::Font5x10Dots && lines != 1 {
+            return Err(LcdError::Other(
+                "Font5x10Dots is only valid on a single-line display".to_string(),
+            ));
+        }
 
         // Use map_err for the MCP error conversion
-        let mcp = Mcp230xx::<I2c, Mcp23017>::new_default(i2c)
+        let mcp = Mcp230xx::<I, Mcp23017>::new(i2c, address)
             .map_err(|e| LcdError::Mcp(format!("{:?}", e)))?;
 
         let mut lcd = CharLCDRGBI2C {
@@ -138,16 +253,19 @@ impl CharLCDRGBI2C {
             column: 0,
             column_align: false,
             message: String::new(),
-            direction: 0, // Assuming 0 for LEFT_TO_RIGHT
+            direction: LCD_MOVELEFT,
+            buttons_raw: Buttons::default(),
+            buttons_stable: Buttons::default(),
+            busy_polling: true,
         };
 
         lcd.setup_pins()?;
-        lcd.initialize()?;
+        lcd.initialize(font)?;
 
         Ok(lcd)
     }
 
-    fn setup_pins(&mut self) -> Result<(), LcdError> {
+    fn setup_pins(&mut self) -> Result<(), LcdError<E>> {
         // Set LCD control pins as outputs
         for pin in [LCD_RS, LCD_E, LCD_D4, LCD_D5, LCD_D6, LCD_D7, LCD_RW] {
             self.mcp.set_direction(pin, Direction::Output)?;
@@ -167,7 +285,7 @@ impl CharLCDRGBI2C {
         Ok(())
     }
 
-    fn initialize(&mut self) -> Result<(), LcdError> {
+    fn initialize(&mut self, font: FontSize) -> Result<(), LcdError<E>> {
         // Wait for LCD to be ready
         sleep(Duration::from_millis(50));
 
@@ -188,7 +306,8 @@ impl CharLCDRGBI2C {
 
         // Initialize display control
         self.display_control = LCD_DISPLAYON | LCD_CURSOROFF | LCD_BLINKOFF;
-        self.display_function = LCD_4BITMODE | LCD_1LINE | LCD_2LINE | LCD_5X8DOTS;
+        let line_bits = if self.lines > 1 { LCD_2LINE } else { LCD_1LINE };
+        self.display_function = LCD_4BITMODE | line_bits | font.function_set_bits();
         self.display_mode = LCD_ENTRYLEFT | LCD_ENTRYSHIFTDECREMENT;
 
         // Write to display control
@@ -205,7 +324,7 @@ impl CharLCDRGBI2C {
         self.row = 0;
         self.column = 0;
         self.column_align = false;
-        self.direction = LEFT_TO_RIGHT;
+        self.direction = LCD_MOVELEFT;
         self.message = String::new();
 
         // Turn off all RGB LEDs initially
@@ -214,7 +333,7 @@ impl CharLCDRGBI2C {
         Ok(())
     }
 
-    fn write4bits(&mut self, value: u8) -> Result<(), LcdError> {
+    fn write4bits(&mut self, value: u8) -> Result<(), LcdError<E>> {
         // Set data pins
         self.mcp.set_output_latch(
             LCD_D4,
@@ -255,17 +374,80 @@ impl CharLCDRGBI2C {
         Ok(())
     }
 
-    fn pulse_enable(&mut self) -> Result<(), LcdError> {
+    fn pulse_enable(&mut self) -> Result<(), LcdError<E>> {
         self.mcp.set_output_latch(LCD_E, Level::Low)?;
         sleep(Duration::from_micros(1));
         self.mcp.set_output_latch(LCD_E, Level::High)?;
         sleep(Duration::from_micros(1));
         self.mcp.set_output_latch(LCD_E, Level::Low)?;
-        sleep(Duration::from_micros(100)); // Commands need > 37us to settle
+        sleep(Duration::from_micros(1));
+        Ok(())
+    }
+
+    /// Waits for the HD44780 to clear its busy flag before returning.
+    ///
+    /// When `busy_polling` is enabled, switches the data pins to inputs and
+    /// reads the busy flag back over `LCD_RW`/`LCD_D7`, retrying up to a
+    /// bounded number of times. If the flag never clears (e.g. boards that
+    /// tie `LCD_RW` to ground) or polling is disabled, falls back to sleeping
+    /// for `fallback_delay`.
+    fn wait_ready(&mut self, fallback_delay: Duration) -> Result<(), LcdError<E>> {
+        if !self.busy_polling {
+            sleep(fallback_delay);
+            return Ok(());
+        }
+
+        const MAX_POLLS: u32 = 2000;
+
+        for pin in [LCD_D4, LCD_D5, LCD_D6, LCD_D7] {
+            self.mcp.set_direction(pin, Direction::Input)?;
+        }
+        self.mcp.set_output_latch(LCD_RS, Level::Low)?;
+        self.mcp.set_output_latch(LCD_RW, Level::High)?;
+
+        let mut ready = false;
+        for _ in 0..MAX_POLLS {
+            // Pulse once to read the upper nibble; DB7 (the busy flag) comes
+            // out on LCD_D7.
+            self.mcp.set_output_latch(LCD_E, Level::High)?;
+            sleep(Duration::from_micros(1));
+            let busy = self.mcp.gpio(LCD_D7)? == Level::High;
+            self.mcp.set_output_latch(LCD_E, Level::Low)?;
+            sleep(Duration::from_micros(1));
+
+            // Pulse again to clock out the low nibble (the address counter);
+            // we don't need its value here.
+            self.mcp.set_output_latch(LCD_E, Level::High)?;
+            sleep(Duration::from_micros(1));
+            self.mcp.set_output_latch(LCD_E, Level::Low)?;
+
+            if !busy {
+                ready = true;
+                break;
+            }
+        }
+
+        self.mcp.set_output_latch(LCD_RW, Level::Low)?;
+        for pin in [LCD_D4, LCD_D5, LCD_D6, LCD_D7] {
+            self.mcp.set_direction(pin, Direction::Output)?;
+        }
+
+        if !ready {
+            sleep(fallback_delay);
+        }
+
         Ok(())
     }
 
-    fn write8(&mut self, value: u8, char_mode: bool) -> Result<(), LcdError> {
+    /// Enables or disables busy-flag polling over `LCD_RW`.
+    ///
+    /// Disable this for boards that wire `LCD_RW` directly to ground
+    /// (write-only); `wait_ready` then always falls back to its fixed delay.
+    pub fn set_busy_polling(&mut self, enabled: bool) {
+        self.busy_polling = enabled;
+    }
+
+    fn write8(&mut self, value: u8, char_mode: bool) -> Result<(), LcdError<E>> {
         // Set the RS pin based on char_mode
         self.mcp
             .set_output_latch(LCD_RS, if char_mode { Level::High } else { Level::Low })?;
@@ -274,31 +456,99 @@ impl CharLCDRGBI2C {
         self.write4bits(value >> 4)?;
         // Send lower 4 bits
         self.write4bits(value & 0x0F)?;
-        Ok(())
+
+        // Commands need > 37us to settle before the next one is issued.
+        self.wait_ready(Duration::from_micros(100))
     }
 
-    fn write_command(&mut self, value: u8) -> Result<(), LcdError> {
+    fn write_command(&mut self, value: u8) -> Result<(), LcdError<E>> {
         self.write8(value, false)?;
         Ok(())
     }
 
-    pub fn clear(&mut self) -> Result<(), LcdError> {
+    pub fn clear(&mut self) -> Result<(), LcdError<E>> {
         self.write_command(LCD_CLEARDISPLAY)?;
-        sleep(Duration::from_millis(3));
+        self.wait_ready(Duration::from_millis(3))?;
         self.row = 0;
         self.column = 0;
         Ok(())
     }
 
-    pub fn home(&mut self) -> Result<(), LcdError> {
+    pub fn home(&mut self) -> Result<(), LcdError<E>> {
         self.write_command(LCD_RETURNHOME)?;
-        sleep(Duration::from_millis(3));
+        self.wait_ready(Duration::from_millis(3))?;
         self.row = 0;
         self.column = 0;
         Ok(())
     }
 
-    pub fn set_color(&mut self, r: u8, g: u8, b: u8) -> Result<(), LcdError> {
+    /// Turns the display on or off without losing the text in DDRAM.
+    pub fn set_display(&mut self, on: bool) -> Result<(), LcdError<E>> {
+        if on {
+            self.display_control |= LCD_DISPLAYON;
+        } else {
+            self.display_control &= !LCD_DISPLAYON;
+        }
+        self.write_command(LCD_DISPLAYCONTROL | self.display_control)
+    }
+
+    /// Shows or hides the underline cursor.
+    pub fn show_cursor(&mut self, on: bool) -> Result<(), LcdError<E>> {
+        if on {
+            self.display_control |= LCD_CURSORON;
+        } else {
+            self.display_control &= !LCD_CURSORON;
+        }
+        self.write_command(LCD_DISPLAYCONTROL | self.display_control)
+    }
+
+    /// Turns the blinking block cursor on or off.
+    pub fn blink(&mut self, on: bool) -> Result<(), LcdError<E>> {
+        if on {
+            self.display_control |= LCD_BLINKON;
+        } else {
+            self.display_control &= !LCD_BLINKON;
+        }
+        self.write_command(LCD_DISPLAYCONTROL | self.display_control)
+    }
+
+    /// Shifts the whole display one position to the left without changing
+    /// DDRAM contents.
+    pub fn scroll_display_left(&mut self) -> Result<(), LcdError<E>> {
+        self.direction = LCD_MOVELEFT;
+        self.write_command(LCD_CURSORSHIFT | LCD_DISPLAYMOVE | self.direction)
+    }
+
+    /// Shifts the whole display one position to the right without changing
+    /// DDRAM contents.
+    pub fn scroll_display_right(&mut self) -> Result<(), LcdError<E>> {
+        self.direction = LCD_MOVERIGHT;
+        self.write_command(LCD_CURSORSHIFT | LCD_DISPLAYMOVE | self.direction)
+    }
+
+    /// Sets whether new characters are entered left-to-right (the default)
+    /// or right-to-left.
+    pub fn set_text_direction(&mut self, left_to_right: bool) -> Result<(), LcdError<E>> {
+        if left_to_right {
+            self.display_mode |= LCD_ENTRYLEFT;
+        } else {
+            self.display_mode &= !LCD_ENTRYLEFT;
+        }
+        self.write_command(LCD_ENTRYMODESET | self.display_mode)
+    }
+
+    /// Enables or disables autoscroll, which shifts existing text instead of
+    /// the cursor as new characters are written.
+    pub fn autoscroll(&mut self, on: bool) -> Result<(), LcdError<E>> {
+        if on {
+            self.display_mode |= LCD_ENTRYSHIFTINCREMENT;
+        } else {
+            self.display_mode &= !LCD_ENTRYSHIFTINCREMENT;
+        }
+        self.write_command(LCD_ENTRYMODESET | self.display_mode)
+    }
+
+    pub fn set_color(&mut self, r: u8, g: u8, b: u8) -> Result<(), LcdError<E>> {
         // Any value > 1 turns LED on (inverse of Python logic)
         // LOW = on for common anode RGB LED
         self.mcp
@@ -312,7 +562,7 @@ impl CharLCDRGBI2C {
         Ok(())
     }
 
-    pub fn set_cursor(&mut self, col: usize, row: usize) -> Result<(), LcdError> {
+    pub fn set_cursor(&mut self, col: usize, row: usize) -> Result<(), LcdError<E>> {
         let row_offsets = [0x00, 0x40, 0x14, 0x54]; // For 16x2 or 20x4 LCD
 
         if row >= self.lines {
@@ -330,7 +580,7 @@ impl CharLCDRGBI2C {
         Ok(())
     }
 
-    pub fn set_backlight(&mut self, on: bool) -> Result<(), LcdError> {
+    pub fn set_backlight(&mut self, on: bool) -> Result<(), LcdError<E>> {
         if on {
             self.mcp.set_direction(LCD_BACKLIGHT, Direction::Output)?;
             self.backlight = true;
@@ -343,7 +593,7 @@ impl CharLCDRGBI2C {
         Ok(())
     }
 
-    pub fn cursor_position(&mut self, mut column: usize, mut row: usize) -> Result<(), LcdError> {
+    pub fn cursor_position(&mut self, mut column: usize, mut row: usize) -> Result<(), LcdError<E>> {
         if row >= self.lines {
             row = self.lines - 1;
         }
@@ -356,7 +606,7 @@ impl CharLCDRGBI2C {
         Ok(())
     }
 
-    pub fn message(&mut self, message: &str) -> Result<(), LcdError> {
+    pub fn message(&mut self, message: &str) -> Result<(), LcdError<E>> {
         self.message = message.to_string();
 
         let mut line = self.row;
@@ -401,4 +651,93 @@ impl CharLCDRGBI2C {
 
         Ok(())
     }
+
+    /// Loads a custom glyph into one of the eight CGRAM slots (0-7).
+    ///
+    /// `bitmap` holds the eight row bytes of the 5x8 character, each using
+    /// the lower 5 bits. Once loaded, the glyph is printed like any other
+    /// character by writing its byte value (0..=7), e.g. via `message`.
+    pub fn create_char(&mut self, location: u8, bitmap: [u8; 8]) -> Result<(), LcdError<E>> {
+        let location = location & 0x07;
+
+        self.write_command(LCD_SETCGRAMADDR | (location << 3))?;
+        for row in bitmap {
+            self.write8(row, true)?;
+        }
+
+        // Writing to CGRAM leaves the DDRAM address pointer dirty, so restore
+        // it before the next `message` call.
+        self.cursor_position(self.column, self.row)?;
+
+        Ok(())
+    }
+
+    /// Reads the five front-panel buttons on GPIOA.
+    ///
+    /// The buttons are active-low with pull-ups enabled, so a `Level::Low`
+    /// reading means the button is currently pressed.
+    pub fn read_buttons(&mut self) -> Result<Buttons, LcdError<E>> {
+        let is_pressed = |level: Level| level == Level::Low;
+
+        Ok(Buttons {
+            left: is_pressed(self.mcp.gpio(BTN_LEFT)?),
+            up: is_pressed(self.mcp.gpio(BTN_UP)?),
+            down: is_pressed(self.mcp.gpio(BTN_DOWN)?),
+            right: is_pressed(self.mcp.gpio(BTN_RIGHT)?),
+            select: is_pressed(self.mcp.gpio(BTN_SELECT)?),
+        })
+    }
+
+    /// Returns whether `button` is currently pressed.
+    pub fn is_pressed(&mut self, button: Button) -> Result<bool, LcdError<E>> {
+        Ok(self.read_buttons()?.is_pressed(button))
+    }
+
+    /// Samples the buttons and returns any `Press`/`Release` events since the
+    /// last call.
+    ///
+    /// A button only generates an event once its level has been read as
+    /// stable across two successive polls, which filters out mechanical
+    /// contact bounce.
+    pub fn poll_events(&mut self) -> Result<Vec<ButtonEvent>, LcdError<E>> {
+        let sample = self.read_buttons()?;
+        let mut events = Vec::new();
+
+        for button in [
+            Button::Left,
+            Button::Up,
+            Button::Down,
+            Button::Right,
+            Button::Select,
+        ] {
+            let now = sample.is_pressed(button);
+            let previous_raw = self.buttons_raw.is_pressed(button);
+            let stable = self.buttons_stable.is_pressed(button);
+
+            if now == previous_raw && now != stable {
+                events.push(if now {
+                    ButtonEvent::Press(button)
+                } else {
+                    ButtonEvent::Release(button)
+                });
+            }
+        }
+
+        self.buttons_raw = sample;
+        for event in &events {
+            let (button, pressed) = match *event {
+                ButtonEvent::Press(b) => (b, true),
+                ButtonEvent::Release(b) => (b, false),
+            };
+            match button {
+                Button::Left => self.buttons_stable.left = pressed,
+                Button::Up => self.buttons_stable.up = pressed,
+                Button::Down => self.buttons_stable.down = pressed,
+                Button::Right => self.buttons_stable.right = pressed,
+                Button::Select => self.buttons_stable.select = pressed,
+            }
+        }
+
+        Ok(events)
+    }
 }